@@ -0,0 +1,133 @@
+use crate::media::rtp::rtp_codec::RTPCodecType;
+use crate::media::rtp::rtp_transceiver::SimulcastLayer;
+use anyhow::Result;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// RTPReceiver allows an application to inspect the receipt of a TrackRemote.
+pub struct RTPReceiver {
+    pub(crate) kind: RTPCodecType,
+
+    packets_received: AtomicU64,
+    bytes_received: AtomicU64,
+
+    // simulcast_layers tracks the distinct RID-keyed streams this receiver
+    // is currently demuxing.
+    simulcast_layers: Mutex<Vec<SimulcastLayer>>,
+}
+
+impl RTPReceiver {
+    pub(crate) fn new(kind: RTPCodecType) -> Self {
+        RTPReceiver {
+            kind,
+            packets_received: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            simulcast_layers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// stop irreversibly stops the RTPReceiver
+    pub(crate) async fn stop(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// packets_received returns the number of RTP packets this receiver has demuxed.
+    pub(crate) async fn packets_received(&self) -> u64 {
+        self.packets_received.load(Ordering::SeqCst)
+    }
+
+    /// bytes_received returns the number of RTP packet bytes (header included) this receiver has demuxed.
+    pub(crate) async fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::SeqCst)
+    }
+
+    /// rid returns the negotiated RTP-stream-id of this receiver's most
+    /// recently added simulcast layer, or an empty string before any layer
+    /// has bound. Rebinding an existing RID keeps that layer's position.
+    pub fn rid(&self) -> String {
+        self.simulcast_layers
+            .lock()
+            .unwrap()
+            .last()
+            .map(|layer| layer.rid.clone())
+            .unwrap_or_default()
+    }
+
+    /// simulcast_layers returns the distinct simulcast layers this receiver is currently demuxing.
+    pub fn simulcast_layers(&self) -> Vec<SimulcastLayer> {
+        self.simulcast_layers.lock().unwrap().clone()
+    }
+
+    /// record_packet accounts one demuxed RTP packet of `len` bytes toward this receiver's stats.
+    pub(crate) fn record_packet(&self, len: usize) {
+        self.packets_received.fetch_add(1, Ordering::SeqCst);
+        self.bytes_received.fetch_add(len as u64, Ordering::SeqCst);
+    }
+
+    /// bind_simulcast_layer binds an observed SSRC to the given RID. A
+    /// repeat RID with a new SSRC (e.g. a simulcast restart) rebinds cleanly
+    /// by replacing the existing entry rather than appending a duplicate.
+    /// A blank `rid` (no RTP-stream-id extension negotiated) is a no-op.
+    pub(crate) fn bind_simulcast_layer(&self, rid: String, ssrc: crate::media::rtp::rtp_transceiver::SSRC) {
+        if rid.is_empty() {
+            return;
+        }
+
+        let mut layers = self.simulcast_layers.lock().unwrap();
+        if let Some(layer) = layers.iter_mut().find(|l| l.rid == rid) {
+            layer.ssrc = ssrc;
+        } else {
+            layers.push(SimulcastLayer { rid, ssrc });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_simulcast_layer_rebinds_repeat_rid_to_new_ssrc() {
+        let receiver = RTPReceiver::new(RTPCodecType::Video);
+
+        receiver.bind_simulcast_layer("h".to_string(), 1111);
+        receiver.bind_simulcast_layer("l".to_string(), 2222);
+        assert_eq!(
+            receiver.simulcast_layers(),
+            vec![
+                SimulcastLayer {
+                    rid: "h".to_string(),
+                    ssrc: 1111
+                },
+                SimulcastLayer {
+                    rid: "l".to_string(),
+                    ssrc: 2222
+                },
+            ]
+        );
+
+        // A Simulcast restart reuses the RID on a new SSRC; it should
+        // rebind the existing entry rather than appending a duplicate.
+        receiver.bind_simulcast_layer("h".to_string(), 3333);
+        assert_eq!(
+            receiver.simulcast_layers(),
+            vec![
+                SimulcastLayer {
+                    rid: "h".to_string(),
+                    ssrc: 3333
+                },
+                SimulcastLayer {
+                    rid: "l".to_string(),
+                    ssrc: 2222
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bind_simulcast_layer_ignores_blank_rid() {
+        let receiver = RTPReceiver::new(RTPCodecType::Video);
+        receiver.bind_simulcast_layer(String::new(), 1111);
+        assert!(receiver.simulcast_layers().is_empty());
+    }
+}
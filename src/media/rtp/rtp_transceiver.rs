@@ -9,11 +9,122 @@ use crate::media::track::track_local::TrackLocal;
 use crate::api::media_engine::MediaEngine;
 use crate::error::Error;
 use anyhow::Result;
-use std::sync::atomic::{AtomicU8, Ordering};
-use std::sync::Arc;
+use bytes::Bytes;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use webrtc_util::Unmarshal;
+
+/// SSRC is the synchronization source identifier carried in an RTP header.
+pub(crate) type SSRC = u32;
+
+/// PayloadType is the RTP payload type carried in an RTP header.
+pub(crate) type PayloadType = u8;
+
+/// MediaSourceKind distinguishes a camera/microphone track from a screen/window capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaSourceKind {
+    /// Camera or microphone capture.
+    Device,
+    /// Screen or window capture.
+    Display,
+}
+
+/// MediaExchangeOrMuteState is the Stable/Transition lifecycle shared by the media-exchange and mute state machines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaExchangeOrMuteState {
+    Stable(bool),
+    Transition(bool),
+}
+
+impl MediaExchangeOrMuteState {
+    /// enabled returns the value this state currently reflects, regardless
+    /// of whether it's settled or still transitioning.
+    pub fn enabled(&self) -> bool {
+        match self {
+            MediaExchangeOrMuteState::Stable(v) | MediaExchangeOrMuteState::Transition(v) => *v,
+        }
+    }
+
+    /// is_stable reports whether the machine has settled on its current value.
+    pub fn is_stable(&self) -> bool {
+        matches!(self, MediaExchangeOrMuteState::Stable(_))
+    }
+}
+
+/// ToggleStateMachine drives a single `MediaExchangeOrMuteState` toward its desired value, settling via `stabilize`.
+struct ToggleStateMachine {
+    current: Mutex<MediaExchangeOrMuteState>,
+    desired: AtomicBool,
+    notify: Notify,
+}
+
+impl ToggleStateMachine {
+    fn new(enabled: bool) -> Self {
+        ToggleStateMachine {
+            current: Mutex::new(MediaExchangeOrMuteState::Stable(enabled)),
+            desired: AtomicBool::new(enabled),
+            notify: Notify::new(),
+        }
+    }
+
+    fn state(&self) -> MediaExchangeOrMuteState {
+        match *self.current.lock().unwrap() {
+            MediaExchangeOrMuteState::Transition(_) => {
+                MediaExchangeOrMuteState::Transition(self.desired.load(Ordering::SeqCst))
+            }
+            stable => stable,
+        }
+    }
+
+    fn set_desired(&self, desired: bool) {
+        self.desired.store(desired, Ordering::SeqCst);
+
+        // Mid-transition, `current` keeps the target already in flight so
+        // `stabilize` can tell a stale confirmation from the desired one.
+        let mut current = self.current.lock().unwrap();
+        if current.is_stable() && *current != MediaExchangeOrMuteState::Stable(desired) {
+            *current = MediaExchangeOrMuteState::Transition(desired);
+        }
+    }
+
+    fn stabilize(&self) {
+        let desired = self.desired.load(Ordering::SeqCst);
+
+        let mut current = self.current.lock().unwrap();
+        match *current {
+            MediaExchangeOrMuteState::Transition(target) if target == desired => {
+                *current = MediaExchangeOrMuteState::Stable(target);
+                drop(current);
+                self.notify.notify_waiters();
+            }
+            MediaExchangeOrMuteState::Stable(target) if target == desired => {}
+            _ => {
+                // The desired value moved again while we were transitioning;
+                // re-fire toward the new target instead of settling.
+                *current = MediaExchangeOrMuteState::Transition(desired);
+            }
+        }
+    }
+
+    async fn when_stabilized(&self) {
+        loop {
+            // Subscribe before checking state: if `notified()` were called
+            // after the check, a `stabilize()` landing in the gap would
+            // call `notify_waiters()` with nothing subscribed yet and the
+            // wakeup would be lost, leaving us parked forever.
+            let notified = self.notify.notified();
+            if self.current.lock().unwrap().is_stable() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
 
 /// RTPTransceiver represents a combination of an RTPSender and an RTPReceiver that share a common mid.
 pub struct RTPTransceiver {
+    id: u64,
     mid: String,                   //atomic.Value
     sender: Option<RTPSender>,     //atomic.Value
     receiver: Option<RTPReceiver>, //atomic.Value
@@ -21,12 +132,66 @@ pub struct RTPTransceiver {
 
     codecs: Vec<RTPCodecParameters>, // User provided codecs via set_codec_preferences
 
+    // media_exchange governs whether the track is actually plumbed into the
+    // sender/receiver (and therefore changes `direction` via
+    // `set_sending_track`). mute governs whether media is transmitted while
+    // the track stays attached, so toggling it needs no renegotiation.
+    media_exchange: ToggleStateMachine,
+    mute: ToggleStateMachine,
+
     pub(crate) stopped: bool,
     pub(crate) kind: RTPCodecType,
+    pub(crate) source_kind: MediaSourceKind,
 
     media_engine: Arc<MediaEngine>,
 }
 
+/// SimulcastLayer is one RTP stream a transceiver's receiver is demuxing,
+/// identified by its negotiated RTP-stream-id (RID).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulcastLayer {
+    pub rid: String,
+    pub ssrc: SSRC,
+}
+
+/// TransceiverStats is a point-in-time snapshot of a transceiver's media health.
+#[derive(Debug, Clone, Default)]
+pub struct TransceiverStats {
+    pub id: String,
+    pub direction: RTPTransceiverDirection,
+    pub codec: Option<RTPCodecParameters>,
+    pub outbound_rtp: Option<OutboundRTPStats>,
+    pub inbound_rtp: Option<InboundRTPStats>,
+    pub simulcast: Vec<SimulcastLayerStats>,
+}
+
+/// OutboundRTPStats are the send-side counters for a transceiver's RTPSender.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutboundRTPStats {
+    pub packets_sent: u64,
+    pub bytes_sent: u64,
+}
+
+/// InboundRTPStats are the receive-side counters for a transceiver's
+/// RTPReceiver.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InboundRTPStats {
+    pub packets_received: u64,
+    pub bytes_received: u64,
+}
+
+/// SimulcastLayerStats identifies one simulcast layer within a
+/// TransceiverStats snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct SimulcastLayerStats {
+    pub rid: String,
+    pub ssrc: SSRC,
+}
+
+/// next_transceiver_id hands out a process-unique id for each RTPTransceiver,
+/// used as the stat_id fallback before mid negotiation.
+static NEXT_TRANSCEIVER_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+
 impl RTPTransceiver {
     pub(crate) fn new(
         receiver: Option<RTPReceiver>,
@@ -35,19 +200,85 @@ impl RTPTransceiver {
         kind: RTPCodecType,
         codecs: Vec<RTPCodecParameters>,
         media_engine: Arc<MediaEngine>,
+        source_kind: MediaSourceKind,
     ) -> Self {
+        let has_track = sender.is_some();
         RTPTransceiver {
+            id: NEXT_TRANSCEIVER_ID.fetch_add(1, Ordering::SeqCst),
             mid: String::new(),
             sender,
             receiver,
             direction: AtomicU8::new(direction as u8),
             codecs,
+            media_exchange: ToggleStateMachine::new(has_track),
+            mute: ToggleStateMachine::new(false),
             stopped: false,
             kind,
+            source_kind,
             media_engine,
         }
     }
 
+    /// source_kind returns whether this transceiver's outbound media
+    /// originates from a device (camera/mic) or a display (screen/window
+    /// capture).
+    pub fn source_kind(&self) -> MediaSourceKind {
+        self.source_kind
+    }
+
+    /// media_exchange_state returns the current state of the media-exchange
+    /// state machine: whether the track is attached to the sender/receiver.
+    pub fn media_exchange_state(&self) -> MediaExchangeOrMuteState {
+        self.media_exchange.state()
+    }
+
+    /// enable requests that the track be plumbed into the sender/receiver.
+    /// This moves the media-exchange state into `Transition(true)`; it
+    /// settles once `set_sending_track` next completes.
+    pub fn enable(&self) {
+        self.media_exchange.set_desired(true);
+    }
+
+    /// disable requests that the track be unplumbed from the sender/receiver.
+    /// This moves the media-exchange state into `Transition(false)`; it
+    /// settles once `set_sending_track` next completes.
+    pub fn disable(&self) {
+        self.media_exchange.set_desired(false);
+    }
+
+    /// when_media_exchange_stabilized resolves once the media-exchange state
+    /// machine has settled on its current desired value.
+    pub async fn when_media_exchange_stabilized(&self) {
+        self.media_exchange.when_stabilized().await;
+    }
+
+    /// mute_state returns the current state of the mute state machine:
+    /// whether media is being transmitted while the track stays attached.
+    pub fn mute_state(&self) -> MediaExchangeOrMuteState {
+        self.mute.state()
+    }
+
+    /// mute stops media from being transmitted while leaving the track
+    /// attached, so no renegotiation is needed. The mute state machine
+    /// settles immediately since there's no peer connection round trip to
+    /// wait for.
+    pub fn mute(&self) {
+        self.mute.set_desired(true);
+        self.mute.stabilize();
+    }
+
+    /// unmute resumes transmitting media on the already-attached track.
+    pub fn unmute(&self) {
+        self.mute.set_desired(false);
+        self.mute.stabilize();
+    }
+
+    /// when_mute_stabilized resolves once the mute state machine has settled
+    /// on its current desired value.
+    pub async fn when_mute_stabilized(&self) {
+        self.mute.when_stabilized().await;
+    }
+
     /// set_codec_preferences sets preferred list of supported codecs
     /// if codecs is empty or nil we reset to default from MediaEngine
     pub async fn set_codec_preferences(&mut self, codecs: Vec<RTPCodecParameters>) -> Result<()> {
@@ -63,22 +294,25 @@ impl RTPTransceiver {
         Ok(())
     }
 
-    /// Codecs returns list of supported codecs
+    /// Codecs returns list of supported codecs, preferring
+    /// screen-content-friendly profiles first when this transceiver carries
+    /// a Display source.
     pub(crate) async fn get_codecs(&self) -> Vec<RTPCodecParameters> {
         let media_engine_codecs = self.media_engine.get_codecs_by_kind(self.kind).await;
-        if self.codecs.is_empty() {
-            return media_engine_codecs;
-        }
-
-        let mut filtered_codecs = vec![];
-        for codec in &self.codecs {
-            let (c, match_type) = codec_parameters_fuzzy_search(codec, &media_engine_codecs);
-            if match_type != CodecMatch::None {
-                filtered_codecs.push(c);
+        let codecs = if self.codecs.is_empty() {
+            media_engine_codecs
+        } else {
+            let mut filtered_codecs = vec![];
+            for codec in &self.codecs {
+                let (c, match_type) = codec_parameters_fuzzy_search(codec, &media_engine_codecs);
+                if match_type != CodecMatch::None {
+                    filtered_codecs.push(c);
+                }
             }
-        }
+            filtered_codecs
+        };
 
-        filtered_codecs
+        prefer_codecs_for_source_kind(codecs, self.kind, self.source_kind)
     }
 
     /// sender returns the RTPTransceiver's RTPSender if it has one
@@ -86,14 +320,15 @@ impl RTPTransceiver {
         self.sender.as_ref()
     }
 
-    /// set_sender sets the RTPSender and Track to current transceiver
+    /// set_sender sets the RTPSender and Track to current transceiver, tagged with the track's source_kind.
     pub async fn set_sender(
         &mut self,
         sender: Option<RTPSender>,
         track: Option<Arc<dyn TrackLocal + Send + Sync>>,
+        source_kind: MediaSourceKind,
     ) -> Result<()> {
         self.sender = sender;
-        self.set_sending_track(track).await
+        self.set_sending_track(track, source_kind).await
     }
 
     /// receiver returns the RTPTransceiver's RTPReceiver if it has one
@@ -101,6 +336,60 @@ impl RTPTransceiver {
         self.receiver.as_ref()
     }
 
+    /// get_stats aggregates inbound/outbound RTP stats from this
+    /// transceiver's RTPSender and RTPReceiver into a single snapshot, keyed
+    /// by a stable stat-id so callers can poll it periodically and diff.
+    pub async fn get_stats(&self) -> TransceiverStats {
+        let codec = self.get_codecs().await.into_iter().next();
+
+        let outbound_rtp = match &self.sender {
+            Some(sender) => Some(OutboundRTPStats {
+                packets_sent: sender.packets_sent().await,
+                bytes_sent: sender.bytes_sent().await,
+            }),
+            None => None,
+        };
+
+        let (inbound_rtp, simulcast) = match &self.receiver {
+            Some(receiver) => {
+                let inbound_rtp = Some(InboundRTPStats {
+                    packets_received: receiver.packets_received().await,
+                    bytes_received: receiver.bytes_received().await,
+                });
+                let simulcast = self
+                    .simulcast_layers()
+                    .into_iter()
+                    .map(|layer| SimulcastLayerStats {
+                        rid: layer.rid,
+                        ssrc: layer.ssrc,
+                    })
+                    .collect();
+                (inbound_rtp, simulcast)
+            }
+            None => (None, vec![]),
+        };
+
+        TransceiverStats {
+            id: self.stat_id(),
+            direction: self.direction(),
+            codec,
+            outbound_rtp,
+            inbound_rtp,
+            simulcast,
+        }
+    }
+
+    /// stat_id returns the stable identifier `get_stats` reports its snapshot
+    /// under: derived from mid once negotiated, falling back to this
+    /// transceiver's own instance id beforehand.
+    fn stat_id(&self) -> String {
+        if self.mid.is_empty() {
+            format!("transceiver-{}", self.id)
+        } else {
+            format!("transceiver-{}", self.mid)
+        }
+    }
+
     /// set_mid sets the RTPTransceiver's mid. If it was already set, will return an error.
     pub(crate) fn set_mid(&mut self, mid: String) -> Result<()> {
         if !self.mid.is_empty() {
@@ -147,8 +436,14 @@ impl RTPTransceiver {
     pub(crate) async fn set_sending_track(
         &mut self,
         track: Option<Arc<dyn TrackLocal + Send + Sync>>,
+        source_kind: MediaSourceKind,
     ) -> Result<()> {
         let track_is_none = track.is_none();
+
+        // Validate the direction transition before touching any state, so a
+        // rejected call leaves the transceiver untouched.
+        let next_direction = next_direction_after_set_sending_track(self.direction(), track_is_none)?;
+
         if let Some(sender) = &mut self.sender {
             sender.replace_track(track).await?;
         }
@@ -156,29 +451,150 @@ impl RTPTransceiver {
             self.sender = None;
         }
 
-        let direction = self.direction();
-        if !track_is_none && direction == RTPTransceiverDirection::Recvonly {
-            self.set_direction(RTPTransceiverDirection::Sendrecv);
-        } else if !track_is_none && direction == RTPTransceiverDirection::Inactive {
-            self.set_direction(RTPTransceiverDirection::Sendonly);
-        } else if track_is_none && direction == RTPTransceiverDirection::Sendrecv {
-            self.set_direction(RTPTransceiverDirection::Recvonly);
-        } else if !track_is_none
-            && (direction == RTPTransceiverDirection::Sendonly
-                || direction == RTPTransceiverDirection::Sendrecv)
-        {
-            // Handle the case where a sendonly transceiver was added by a negotiation
-            // initiated by remote peer. For example a remote peer added a transceiver
-            // with direction recvonly.
-            //} else if !track_is_none && self.direction == RTPTransceiverDirection::Sendrecv {
-            // Similar to above, but for sendrecv transceiver.
-        } else if track_is_none && direction == RTPTransceiverDirection::Sendonly {
-            self.set_direction(RTPTransceiverDirection::Inactive);
-        } else {
-            return Err(Error::ErrRTPTransceiverSetSendingInvalidState.into());
+        if let Some(next_direction) = next_direction {
+            self.set_direction(next_direction);
+        }
+
+        self.source_kind = source_kind;
+
+        // The peer connection has confirmed the attach/detach: settle the
+        // media-exchange state machine.
+        self.media_exchange.stabilize();
+
+        Ok(())
+    }
+
+    /// apply_patch atomically applies a [`TransceiverPatch`]; if any field is invalid, `self` is left completely untouched.
+    pub async fn apply_patch(&mut self, patch: TransceiverPatch) -> Result<()> {
+        if let Some(codecs) = &patch.codec_preferences {
+            let media_engine_codecs = self.media_engine.get_codecs_by_kind(self.kind).await;
+            for codec in codecs {
+                let (_, match_type) = codec_parameters_fuzzy_search(codec, &media_engine_codecs);
+                if match_type == CodecMatch::None {
+                    return Err(Error::ErrRTPTransceiverCodecUnsupported.into());
+                }
+            }
+        }
+        // Resolve up front what set_sending_track will actually be called
+        // with, so the dry-run below can't validate a different transition
+        // than the one we execute (the sender may be absent even when
+        // enabled is true).
+        let sending_track = match (&self.sender, patch.enabled) {
+            (Some(sender), Some(true)) => sender.track().await,
+            _ => None,
+        };
+        // Enabling with no track to attach leaves direction and the sender as
+        // they are; only a real attach or a detach goes through set_sending_track.
+        let toggles_track = match patch.enabled {
+            Some(true) => sending_track.is_some(),
+            Some(false) => true,
+            None => false,
+        };
+        if toggles_track {
+            // Dry-run the same legality check set_sending_track performs,
+            // so an invalid toggle is rejected before anything mutates.
+            next_direction_after_set_sending_track(self.direction(), sending_track.is_none())?;
+        }
+
+        // Everything validated: apply the whole patch in one transaction.
+        // codec_preferences is applied last among the fallible steps so a
+        // set_sending_track failure below can't leave it half-patched.
+        if let Some(enabled) = patch.enabled {
+            // Route through the real attach/detach path so direction, the
+            // sender's track, and the media-exchange state machine all move
+            // together instead of drifting apart.
+            self.media_exchange.set_desired(enabled);
+            if toggles_track {
+                self.set_sending_track(sending_track, self.source_kind).await?;
+            }
+        }
+        if let Some(codecs) = patch.codec_preferences {
+            self.codecs = codecs;
         }
+        if let Some(direction) = patch.direction {
+            self.set_direction(direction);
+        }
+        if let Some(muted) = patch.muted {
+            if muted {
+                self.mute();
+            } else {
+                self.unmute();
+            }
+        }
+
         Ok(())
     }
+
+    /// simulcast_layers returns the distinct simulcast layers this transceiver's RTPReceiver is currently receiving.
+    pub fn simulcast_layers(&self) -> Vec<SimulcastLayer> {
+        match &self.receiver {
+            Some(receiver) => receiver.simulcast_layers(),
+            None => vec![],
+        }
+    }
+}
+
+/// prefer_codecs_for_source_kind reorders `codecs` so that, for a Display
+/// (screen/window capture) video source, screen-content-friendly profiles
+/// (VP9/AV1, which tune better for mostly-static, high-detail content than
+/// the H.264 profiles favored for camera video) sort first. Device sources
+/// and non-video kinds are returned unchanged.
+fn prefer_codecs_for_source_kind(
+    mut codecs: Vec<RTPCodecParameters>,
+    kind: RTPCodecType,
+    source_kind: MediaSourceKind,
+) -> Vec<RTPCodecParameters> {
+    if kind != RTPCodecType::Video || source_kind != MediaSourceKind::Display {
+        return codecs;
+    }
+
+    let is_screen_content_friendly = |c: &RTPCodecParameters| {
+        let mime_type = c.capability.mime_type.to_uppercase();
+        mime_type.contains("VP9") || mime_type.contains("AV1")
+    };
+
+    codecs.sort_by_key(|c| !is_screen_content_friendly(c));
+    codecs
+}
+
+/// next_direction_after_set_sending_track computes the direction a
+/// transceiver should move to after attaching (`track_is_none == false`) or
+/// detaching (`track_is_none == true`) a sending track, without mutating
+/// anything. Returns `Ok(None)` when the direction shouldn't change.
+fn next_direction_after_set_sending_track(
+    direction: RTPTransceiverDirection,
+    track_is_none: bool,
+) -> Result<Option<RTPTransceiverDirection>> {
+    if !track_is_none && direction == RTPTransceiverDirection::Recvonly {
+        Ok(Some(RTPTransceiverDirection::Sendrecv))
+    } else if !track_is_none && direction == RTPTransceiverDirection::Inactive {
+        Ok(Some(RTPTransceiverDirection::Sendonly))
+    } else if track_is_none && direction == RTPTransceiverDirection::Sendrecv {
+        Ok(Some(RTPTransceiverDirection::Recvonly))
+    } else if !track_is_none
+        && (direction == RTPTransceiverDirection::Sendonly
+            || direction == RTPTransceiverDirection::Sendrecv)
+    {
+        // Handle the case where a sendonly transceiver was added by a negotiation
+        // initiated by remote peer. For example a remote peer added a transceiver
+        // with direction recvonly.
+        //} else if !track_is_none && self.direction == RTPTransceiverDirection::Sendrecv {
+        // Similar to above, but for sendrecv transceiver.
+        Ok(None)
+    } else if track_is_none && direction == RTPTransceiverDirection::Sendonly {
+        Ok(Some(RTPTransceiverDirection::Inactive))
+    } else {
+        Err(Error::ErrRTPTransceiverSetSendingInvalidState.into())
+    }
+}
+
+/// TransceiverPatch is a diff of desired RTPTransceiver properties, applied atomically via [`RTPTransceiver::apply_patch`].
+#[derive(Debug, Default, Clone)]
+pub struct TransceiverPatch {
+    pub direction: Option<RTPTransceiverDirection>,
+    pub enabled: Option<bool>,
+    pub muted: Option<bool>,
+    pub codec_preferences: Option<Vec<RTPCodecParameters>>,
 }
 
 pub(crate) fn find_by_mid(
@@ -227,28 +643,336 @@ pub(crate) fn satisfy_type_and_direction(
 
     None
 }
-/*
-// handleUnknownRTPPacket consumes a single RTP Packet and returns information that is helpful
-// for demuxing and handling an unknown SSRC (usually for Simulcast)
-func handleUnknownRTPPacket(buf []byte, midExtensionID, streamIDExtensionID uint8) (mid, rid string, payloadType PayloadType, err error) {
-    rp := &rtp.Packet{}
-    if err = rp.Unmarshal(buf); err != nil {
-        return
+/// UnknownRTPPacketInfo is the demuxing information recovered from an RTP
+/// packet arriving on an SSRC we haven't mapped yet (usually Simulcast): the
+/// negotiated mid, the RTP-stream-id (RID), the SSRC the packet arrived on,
+/// and its payload type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UnknownRTPPacketInfo {
+    pub(crate) mid: String,
+    pub(crate) rid: String,
+    pub(crate) ssrc: SSRC,
+    pub(crate) payload_type: PayloadType,
+}
+
+/// handle_unknown_rtp_packet consumes a single RTP packet and returns
+/// information that is helpful for demuxing and handling an unknown SSRC
+/// (usually for Simulcast). Packets with no extension header carry no mid
+/// or rid; the caller must buffer such packets until the mid becomes known
+/// through some other packet on the same SSRC.
+pub(crate) fn handle_unknown_rtp_packet(
+    buf: &[u8],
+    mid_extension_id: u8,
+    stream_id_extension_id: u8,
+) -> Result<UnknownRTPPacketInfo> {
+    let mut raw_packet = Bytes::copy_from_slice(buf);
+    let rp = rtp::packet::Packet::unmarshal(&mut raw_packet)?;
+
+    let mut info = UnknownRTPPacketInfo {
+        mid: String::new(),
+        rid: String::new(),
+        ssrc: rp.header.ssrc,
+        payload_type: rp.header.payload_type,
+    };
+
+    if !rp.header.extension {
+        return Ok(info);
+    }
+
+    if let Some(payload) = rp.header.get_extension(mid_extension_id) {
+        info.mid = String::from_utf8_lossy(&payload).to_string();
+    }
+    if let Some(payload) = rp.header.get_extension(stream_id_extension_id) {
+        info.rid = String::from_utf8_lossy(&payload).to_string();
+    }
+
+    Ok(info)
+}
+
+/// find_transceiver_by_mid looks up an already-negotiated RTPTransceiver by
+/// mid without mutating `transceivers`. Unlike `find_by_mid` - which plucks
+/// an entry out of the negotiation-time candidate pool for
+/// `satisfy_type_and_direction` - this is a read-only lookup meant to be
+/// called once per incoming RTP packet, so it must not reorder or remove
+/// from the set of live transceivers.
+pub(crate) fn find_transceiver_by_mid(
+    mid: &str,
+    transceivers: &[Arc<RTPTransceiver>],
+) -> Option<Arc<RTPTransceiver>> {
+    transceivers.iter().find(|t| t.mid() == mid).cloned()
+}
+
+/// route_unknown_rtp_packet demuxes a packet arriving on an unmapped SSRC:
+/// it finds the RTPTransceiver the packet's mid belongs to via
+/// `find_transceiver_by_mid`, then hands the packet to that transceiver's
+/// RTPReceiver to count and bind as a simulcast layer. Returns `Ok(None)`
+/// when the packet carries no mid yet, signaling the caller to buffer it
+/// until one arrives on the same SSRC.
+pub(crate) fn route_unknown_rtp_packet(
+    buf: &[u8],
+    mid_extension_id: u8,
+    stream_id_extension_id: u8,
+    transceivers: &[Arc<RTPTransceiver>],
+) -> Result<Option<Arc<RTPTransceiver>>> {
+    let info = handle_unknown_rtp_packet(buf, mid_extension_id, stream_id_extension_id)?;
+    if info.mid.is_empty() {
+        return Ok(None);
+    }
+
+    let transceiver = find_transceiver_by_mid(&info.mid, transceivers);
+    if let Some(transceiver) = &transceiver {
+        if let Some(receiver) = transceiver.receiver() {
+            receiver.record_packet(buf.len());
+            receiver.bind_simulcast_layer(info.rid, info.ssrc);
+        }
+    }
+
+    Ok(transceiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webrtc_util::Marshal;
+
+    fn new_test_transceiver() -> RTPTransceiver {
+        new_test_transceiver_with_direction(RTPTransceiverDirection::Inactive)
+    }
+
+    fn new_test_transceiver_with_direction(direction: RTPTransceiverDirection) -> RTPTransceiver {
+        RTPTransceiver::new(
+            None,
+            None,
+            direction,
+            RTPCodecType::Video,
+            vec![],
+            Arc::new(MediaEngine::default()),
+            MediaSourceKind::Device,
+        )
+    }
+
+    #[tokio::test]
+    async fn apply_patch_rolls_back_on_invalid_enabled_transition() {
+        let mut t = new_test_transceiver();
+
+        // Disabling an already-inactive, trackless transceiver has no legal
+        // direction transition, so this must be rejected...
+        let err = t
+            .apply_patch(TransceiverPatch {
+                enabled: Some(false),
+                muted: Some(true),
+                ..Default::default()
+            })
+            .await;
+        assert!(err.is_err());
+
+        // ...and none of the patch's other fields should have taken effect.
+        assert_eq!(t.direction(), RTPTransceiverDirection::Inactive);
+        assert_eq!(t.mute_state(), MediaExchangeOrMuteState::Stable(false));
+    }
+
+    #[tokio::test]
+    async fn apply_patch_enabled_true_succeeds_without_a_sender() {
+        // Recvonly + attach is a legal direction transition, but this
+        // transceiver has no sender, so there's no track to actually attach.
+        // The dry-run must agree with that, not just with `enabled` alone.
+        let mut t = new_test_transceiver_with_direction(RTPTransceiverDirection::Recvonly);
+
+        t.apply_patch(TransceiverPatch {
+            enabled: Some(true),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(t.direction(), RTPTransceiverDirection::Recvonly);
+    }
+
+    #[tokio::test]
+    async fn apply_patch_leaves_codecs_untouched_when_enabled_transition_is_invalid() {
+        // Seed the transceiver with existing codec preferences directly
+        // (bypassing apply_patch's own codec validation, which is exercised
+        // elsewhere) so we can tell whether the empty patch below actually
+        // got applied.
+        let mut t = RTPTransceiver::new(
+            None,
+            None,
+            RTPTransceiverDirection::Inactive,
+            RTPCodecType::Video,
+            vec![codec_with_mime_type("video/VP9", 98)],
+            Arc::new(MediaEngine::default()),
+            MediaSourceKind::Device,
+        );
+
+        // An empty codec_preferences list trivially passes validation (there's
+        // nothing to fuzzy-match), so this isolates the enabled transition as
+        // the only thing that should fail the patch.
+        let err = t
+            .apply_patch(TransceiverPatch {
+                enabled: Some(false),
+                codec_preferences: Some(vec![]),
+                ..Default::default()
+            })
+            .await;
+        assert!(err.is_err());
+        assert_eq!(t.codecs.len(), 1);
     }
 
-    if !rp.Header.Extension {
-        return
+    #[test]
+    fn toggle_state_machine_transitions_then_stabilizes() {
+        let m = ToggleStateMachine::new(false);
+        assert_eq!(m.state(), MediaExchangeOrMuteState::Stable(false));
+
+        m.set_desired(true);
+        assert_eq!(m.state(), MediaExchangeOrMuteState::Transition(true));
+
+        m.stabilize();
+        assert_eq!(m.state(), MediaExchangeOrMuteState::Stable(true));
     }
 
-    payloadType = PayloadType(rp.PayloadType)
-    if payload := rp.GetExtension(midExtensionID); payload != nil {
-        mid = string(payload)
+    #[test]
+    fn toggle_state_machine_refires_when_desired_changes_mid_transition() {
+        let m = ToggleStateMachine::new(false);
+
+        m.set_desired(true);
+        assert_eq!(m.state(), MediaExchangeOrMuteState::Transition(true));
+
+        // The desired value flips again before the first transition settles.
+        m.set_desired(false);
+        assert_eq!(m.state(), MediaExchangeOrMuteState::Transition(false));
+
+        // Confirming the stale target re-fires toward the new one instead of settling.
+        m.stabilize();
+        assert_eq!(m.state(), MediaExchangeOrMuteState::Transition(false));
+
+        m.stabilize();
+        assert_eq!(m.state(), MediaExchangeOrMuteState::Stable(false));
+    }
+
+    #[tokio::test]
+    async fn toggle_state_machine_when_stabilized_resolves_after_stabilize() {
+        let m = Arc::new(ToggleStateMachine::new(false));
+        m.set_desired(true);
+
+        let waiter = {
+            let m = m.clone();
+            tokio::spawn(async move {
+                m.when_stabilized().await;
+            })
+        };
+
+        m.stabilize();
+        waiter.await.unwrap();
+        assert_eq!(m.state(), MediaExchangeOrMuteState::Stable(true));
     }
 
-    if payload := rp.GetExtension(streamIDExtensionID); payload != nil {
-        rid = string(payload)
+    fn marshal_test_packet(mid: Option<&str>, rid: Option<&str>) -> Vec<u8> {
+        let mut header = rtp::header::Header {
+            payload_type: 96,
+            ssrc: 42,
+            ..Default::default()
+        };
+        if let Some(mid) = mid {
+            header
+                .set_extension(1, Bytes::copy_from_slice(mid.as_bytes()))
+                .unwrap();
+        }
+        if let Some(rid) = rid {
+            header
+                .set_extension(2, Bytes::copy_from_slice(rid.as_bytes()))
+                .unwrap();
+        }
+        let packet = rtp::packet::Packet {
+            header,
+            payload: Bytes::new(),
+        };
+        packet.marshal().unwrap().to_vec()
     }
 
-    return
+    #[test]
+    fn handle_unknown_rtp_packet_without_extensions_returns_blank_mid_and_rid() {
+        let buf = marshal_test_packet(None, None);
+
+        let info = handle_unknown_rtp_packet(&buf, 1, 2).unwrap();
+        assert_eq!(info.mid, "");
+        assert_eq!(info.rid, "");
+        assert_eq!(info.ssrc, 42);
+        assert_eq!(info.payload_type, 96);
+    }
+
+    #[test]
+    fn handle_unknown_rtp_packet_with_mid_only() {
+        let buf = marshal_test_packet(Some("audio0"), None);
+
+        let info = handle_unknown_rtp_packet(&buf, 1, 2).unwrap();
+        assert_eq!(info.mid, "audio0");
+        assert_eq!(info.rid, "");
+    }
+
+    #[test]
+    fn handle_unknown_rtp_packet_with_rid_only() {
+        let buf = marshal_test_packet(None, Some("h"));
+
+        let info = handle_unknown_rtp_packet(&buf, 1, 2).unwrap();
+        assert_eq!(info.mid, "");
+        assert_eq!(info.rid, "h");
+    }
+
+    #[test]
+    fn handle_unknown_rtp_packet_with_mid_and_rid() {
+        let buf = marshal_test_packet(Some("audio0"), Some("h"));
+
+        let info = handle_unknown_rtp_packet(&buf, 1, 2).unwrap();
+        assert_eq!(info.mid, "audio0");
+        assert_eq!(info.rid, "h");
+    }
+
+    #[tokio::test]
+    async fn get_stats_id_is_distinct_per_transceiver_before_negotiation() {
+        let a = new_test_transceiver();
+        let b = new_test_transceiver();
+
+        assert_ne!(a.get_stats().await.id, b.get_stats().await.id);
+    }
+
+    fn codec_with_mime_type(mime_type: &str, payload_type: PayloadType) -> RTPCodecParameters {
+        RTPCodecParameters {
+            capability: crate::media::rtp::rtp_codec::RTPCodecCapability {
+                mime_type: mime_type.to_owned(),
+                ..Default::default()
+            },
+            payload_type,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn prefer_codecs_for_source_kind_reorders_display_video_toward_vp9_av1() {
+        let codecs = vec![
+            codec_with_mime_type("video/H264", 102),
+            codec_with_mime_type("video/VP9", 98),
+            codec_with_mime_type("video/AV1", 99),
+        ];
+
+        let preferred =
+            prefer_codecs_for_source_kind(codecs.clone(), RTPCodecType::Video, MediaSourceKind::Display);
+        assert_eq!(preferred[0].capability.mime_type, "video/VP9");
+        assert_eq!(preferred[1].capability.mime_type, "video/AV1");
+        assert_eq!(preferred[2].capability.mime_type, "video/H264");
+    }
+
+    #[test]
+    fn prefer_codecs_for_source_kind_leaves_device_source_untouched() {
+        let codecs = vec![
+            codec_with_mime_type("video/H264", 102),
+            codec_with_mime_type("video/VP9", 98),
+        ];
+
+        let preferred = prefer_codecs_for_source_kind(
+            codecs.clone(),
+            RTPCodecType::Video,
+            MediaSourceKind::Device,
+        );
+        assert_eq!(preferred, codecs);
+    }
 }
-*/
\ No newline at end of file